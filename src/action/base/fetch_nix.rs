@@ -0,0 +1,366 @@
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use url::Url;
+
+use crate::{
+    action::{Action, ActionDescription, ActionState},
+    settings::{UrlOrPath, SSL_CERT_FILE_RECEIPT_PATH},
+};
+
+/// Fetch the Nix package archive and unpack it into a temporary directory, ready for
+/// [`MoveUnpackedNix`][crate::action::base::MoveUnpackedNix] to relocate into `/nix`.
+///
+/// `url` may point at an `https://` (or `http://`) location, in which case it is downloaded, or
+/// it may be a `file://` URL or bare filesystem path, in which case the archive is read directly
+/// off disk. The latter allows installing on machines with no network access, provided the
+/// archive has been staged there ahead of time.
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct FetchNix {
+    url: UrlOrPath,
+    dest: PathBuf,
+    ssl_cert_file: Option<PathBuf>,
+    proxy: Option<Url>,
+    nix_package_sha256: Option<String>,
+    action_state: ActionState,
+}
+
+impl FetchNix {
+    #[tracing::instrument(skip_all)]
+    pub async fn plan(
+        url: UrlOrPath,
+        dest: PathBuf,
+        ssl_cert_file: Option<PathBuf>,
+        proxy: Option<Url>,
+        nix_package_sha256: Option<String>,
+    ) -> Result<Self, FetchNixError> {
+        if let Some(path) = url.as_local_path() {
+            if !path.exists() {
+                return Err(FetchNixError::LocalArchiveNotFound(path));
+            }
+
+            let metadata = tokio::fs::metadata(&path)
+                .await
+                .map_err(|e| FetchNixError::ReadLocalArchive(path.clone(), e))?;
+            if !metadata.is_file() {
+                return Err(FetchNixError::LocalArchiveNotFound(path));
+            }
+
+            // Make sure the archive is actually readable and starts with the xz magic bytes
+            // now, so a permission-denied or non-xz local archive is caught before
+            // `ProvisionNix::execute` has already started mutating the system concurrently.
+            validate_local_archive(&path).await?;
+        }
+
+        let ssl_cert_file = match ssl_cert_file {
+            Some(path) => Some(
+                tokio::fs::canonicalize(&path)
+                    .await
+                    .map_err(|e| FetchNixError::CanonicalizeSslCertFile(path, e))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            url,
+            dest,
+            ssl_cert_file,
+            proxy,
+            nix_package_sha256,
+            action_state: ActionState::Uncompleted,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "fetch_nix")]
+impl Action for FetchNix {
+    fn tracing_synopsis(&self) -> String {
+        format!("Fetch Nix from `{}`", self.url)
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            self.tracing_synopsis(),
+            vec![format!(
+                "Unpack the Nix package archive into `{}`",
+                self.dest.display()
+            )],
+        )]
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn execute(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Self {
+            url,
+            dest,
+            ssl_cert_file,
+            proxy,
+            nix_package_sha256,
+            ..
+        } = self;
+
+        match url.as_local_path() {
+            Some(path) => unpack_archive(&path, dest, nix_package_sha256.as_deref())
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?,
+            None => fetch_and_unpack_archive(
+                url,
+                dest,
+                ssl_cert_file.as_deref(),
+                proxy.as_ref(),
+                nix_package_sha256.as_deref(),
+            )
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?,
+        }
+
+        if let Some(ssl_cert_file) = ssl_cert_file {
+            persist_ssl_cert_file_receipt(ssl_cert_file)
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        }
+
+        Ok(())
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            format!("Remove the unpacked Nix archive from `{}`", self.dest.display()),
+            vec![],
+        )]
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn revert(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.dest.exists() {
+            tokio::fs::remove_dir_all(&self.dest)
+                .await
+                .map_err(|e| FetchNixError::RemoveDestination(self.dest.clone(), e))?;
+        }
+
+        if self.ssl_cert_file.is_some() {
+            remove_ssl_cert_file_receipt()
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+        }
+
+        Ok(())
+    }
+
+    fn action_state(&self) -> ActionState {
+        self.action_state
+    }
+
+    fn set_action_state(&mut self, action_state: ActionState) {
+        self.action_state = action_state;
+    }
+}
+
+/// The first six bytes of an xz stream: <https://tukaani.org/xz/xz-file-format.txt>.
+const XZ_MAGIC: [u8; 6] = [0xFD, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Open `path` and check it starts with the xz magic bytes, so we can report an unreadable or
+/// non-archive file during `plan` rather than partway through a concurrent `execute`.
+async fn validate_local_archive(path: &Path) -> Result<(), FetchNixError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| FetchNixError::ReadLocalArchive(path.to_path_buf(), e))?;
+
+    let mut magic = [0u8; XZ_MAGIC.len()];
+    file.read_exact(&mut magic)
+        .await
+        .map_err(|e| FetchNixError::ReadLocalArchive(path.to_path_buf(), e))?;
+
+    if magic != XZ_MAGIC {
+        return Err(FetchNixError::NotAnXzArchive(path.to_path_buf()));
+    }
+
+    Ok(())
+}
+
+/// Record the configured `--ssl-cert-file` at [`SSL_CERT_FILE_RECEIPT_PATH`], so a later
+/// `nix-installer export` process (which has no other way to learn what this install was
+/// configured with) can still prefer it over the autodetected CA bundle candidates.
+async fn persist_ssl_cert_file_receipt(ssl_cert_file: &Path) -> Result<(), FetchNixError> {
+    let receipt_path = PathBuf::from(SSL_CERT_FILE_RECEIPT_PATH);
+    if let Some(parent) = receipt_path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| FetchNixError::CreateDirectory(parent.to_path_buf(), e))?;
+    }
+
+    tokio::fs::write(&receipt_path, ssl_cert_file.as_os_str().as_encoded_bytes())
+        .await
+        .map_err(|e| FetchNixError::WriteSslCertFileReceipt(receipt_path, e))?;
+
+    Ok(())
+}
+
+/// Undo [`persist_ssl_cert_file_receipt`], so a reverted install doesn't leave a stale
+/// `--ssl-cert-file` behind for a later, unconfigured install's `nix-installer export` to
+/// mistakenly honor.
+async fn remove_ssl_cert_file_receipt() -> Result<(), FetchNixError> {
+    match tokio::fs::remove_file(SSL_CERT_FILE_RECEIPT_PATH).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(FetchNixError::RemoveSslCertFileReceipt(
+            PathBuf::from(SSL_CERT_FILE_RECEIPT_PATH),
+            e,
+        )),
+    }
+}
+
+/// Read the local archive at `path` and unpack it into `dest`.
+async fn unpack_archive(
+    path: &Path,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+) -> Result<(), FetchNixError> {
+    tokio::fs::create_dir_all(dest)
+        .await
+        .map_err(|e| FetchNixError::CreateDirectory(dest.to_path_buf(), e))?;
+
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| FetchNixError::ReadLocalArchive(path.to_path_buf(), e))?;
+
+    if let Some(expected_sha256) = expected_sha256 {
+        verify_sha256(expected_sha256, &bytes)?;
+    }
+
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let decoder = xz2::read::XzDecoder::new(bytes.as_slice());
+        let mut archive = tar::Archive::new(decoder);
+        archive.unpack(&dest)
+    })
+    .await
+    .map_err(FetchNixError::Join)?
+    .map_err(FetchNixError::Unarchive)?;
+
+    Ok(())
+}
+
+/// Download the archive at `url` and unpack it into `dest`.
+async fn fetch_and_unpack_archive(
+    url: &UrlOrPath,
+    dest: &Path,
+    ssl_cert_file: Option<&Path>,
+    proxy: Option<&Url>,
+    expected_sha256: Option<&str>,
+) -> Result<(), FetchNixError> {
+    let UrlOrPath::Url(url) = url else {
+        unreachable!("fetch_and_unpack_archive is only called for non-local URLs")
+    };
+
+    tokio::fs::create_dir_all(dest)
+        .await
+        .map_err(|e| FetchNixError::CreateDirectory(dest.to_path_buf(), e))?;
+
+    let mut client_builder = reqwest::Client::builder();
+
+    if let Some(proxy) = proxy {
+        client_builder =
+            client_builder.proxy(reqwest::Proxy::all(proxy.clone()).map_err(FetchNixError::Reqwest)?);
+    }
+
+    if let Some(ssl_cert_file) = ssl_cert_file {
+        let cert_bytes = tokio::fs::read(ssl_cert_file)
+            .await
+            .map_err(|e| FetchNixError::ReadSslCertFile(ssl_cert_file.to_path_buf(), e))?;
+        let cert = reqwest::Certificate::from_pem(&cert_bytes)
+            .map_err(|e| FetchNixError::ParseSslCertFile(ssl_cert_file.to_path_buf(), e))?;
+        client_builder = client_builder.add_root_certificate(cert);
+    }
+
+    let client = client_builder.build().map_err(FetchNixError::Reqwest)?;
+    let res = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(FetchNixError::Reqwest)?
+        .error_for_status()
+        .map_err(FetchNixError::Reqwest)?;
+    let bytes = res.bytes().await.map_err(FetchNixError::Reqwest)?;
+
+    if let Some(expected_sha256) = expected_sha256 {
+        verify_sha256(expected_sha256, &bytes)?;
+    }
+
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let decoder = xz2::read::XzDecoder::new(bytes.as_ref());
+        let mut archive = tar::Archive::new(decoder);
+        archive.unpack(&dest)
+    })
+    .await
+    .map_err(FetchNixError::Join)?
+    .map_err(FetchNixError::Unarchive)?;
+
+    Ok(())
+}
+
+/// Verify that `bytes` hashes to `expected`, which may be an SRI digest (`sha256-<base64>`) or a
+/// bare hex-encoded SHA-256 digest.
+fn verify_sha256(expected: &str, bytes: &[u8]) -> Result<(), FetchNixError> {
+    use sha2::{Digest, Sha256};
+
+    let expected_bytes = match expected.strip_prefix("sha256-") {
+        Some(base64_digest) => base64::engine::general_purpose::STANDARD
+            .decode(base64_digest)
+            .map_err(|_| FetchNixError::InvalidDigest(expected.to_string()))?,
+        None => hex::decode(expected)
+            .map_err(|_| FetchNixError::InvalidDigest(expected.to_string()))?,
+    };
+
+    let actual_bytes = Sha256::digest(bytes).to_vec();
+
+    if actual_bytes == expected_bytes {
+        Ok(())
+    } else {
+        Err(FetchNixError::HashMismatch {
+            expected: expected.to_string(),
+            actual: format!(
+                "sha256-{}",
+                base64::engine::general_purpose::STANDARD.encode(actual_bytes)
+            ),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FetchNixError {
+    #[error("Local Nix package archive `{0}` does not exist or is not a file")]
+    LocalArchiveNotFound(PathBuf),
+    #[error("Reading local Nix package archive `{0}`")]
+    ReadLocalArchive(PathBuf, #[source] std::io::Error),
+    #[error("`{0}` does not look like an xz-compressed tar archive")]
+    NotAnXzArchive(PathBuf),
+    #[error("Canonicalizing SSL certificate file `{0}`")]
+    CanonicalizeSslCertFile(PathBuf, #[source] std::io::Error),
+    #[error("Reading SSL certificate file `{0}`")]
+    ReadSslCertFile(PathBuf, #[source] std::io::Error),
+    #[error("Parsing SSL certificate file `{0}`")]
+    ParseSslCertFile(PathBuf, #[source] reqwest::Error),
+    #[error("Creating directory `{0}`")]
+    CreateDirectory(PathBuf, #[source] std::io::Error),
+    #[error("Removing destination `{0}`")]
+    RemoveDestination(PathBuf, #[source] std::io::Error),
+    #[error("Writing configured SSL certificate file receipt to `{0}`")]
+    WriteSslCertFileReceipt(PathBuf, #[source] std::io::Error),
+    #[error("Removing configured SSL certificate file receipt at `{0}`")]
+    RemoveSslCertFileReceipt(PathBuf, #[source] std::io::Error),
+    #[error("Unpacking Nix package archive")]
+    Unarchive(#[source] std::io::Error),
+    #[error("`{0}` is not a valid SHA-256 digest (expected an SRI `sha256-...` or hex string)")]
+    InvalidDigest(String),
+    #[error("Nix package archive hash mismatch: expected `{expected}`, got `{actual}`")]
+    HashMismatch { expected: String, actual: String },
+    #[error("Joining spawned blocking task")]
+    Join(#[source] tokio::task::JoinError),
+    #[error("Requesting Nix package archive")]
+    Reqwest(#[source] reqwest::Error),
+}
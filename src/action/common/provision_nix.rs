@@ -13,6 +13,22 @@ use tokio::task::JoinError;
 
 use super::{CreateNixTree, CreateNixTreeError, CreateUsersAndGroup, CreateUsersAndGroupError};
 
+const TEMP_INSTALL_DIR: &str = "/nix/temp-install-dir";
+
+/// Best-effort cleanup of a partially-fetched Nix package archive left behind by the concurrent
+/// [`FetchNix`] task, eg. because a sibling action failed or the downloaded archive's hash
+/// didn't match. Callers must wait for that task to actually finish first — see the comments at
+/// the call sites.
+async fn remove_temp_install_dir() {
+    if let Err(err) = tokio::fs::remove_dir_all(TEMP_INSTALL_DIR).await {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(
+                "Failed to clean up partial Nix fetch at `{TEMP_INSTALL_DIR}`: {err}"
+            );
+        }
+    }
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct ProvisionNix {
     fetch_nix: FetchNix,
@@ -29,7 +45,10 @@ impl ProvisionNix {
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let fetch_nix = FetchNix::plan(
             settings.nix_package_url.clone(),
-            PathBuf::from("/nix/temp-install-dir"),
+            PathBuf::from(TEMP_INSTALL_DIR),
+            settings.ssl_cert_file.clone(),
+            settings.proxy.clone(),
+            settings.nix_package_sha256.clone(),
         )
         .await
         .map_err(|e| e.boxed())?;
@@ -37,7 +56,7 @@ impl ProvisionNix {
             .await
             .map_err(|e| e.boxed())?;
         let create_nix_tree = CreateNixTree::plan().await?;
-        let move_unpacked_nix = MoveUnpackedNix::plan(PathBuf::from("/nix/temp-install-dir"))
+        let move_unpacked_nix = MoveUnpackedNix::plan(PathBuf::from(TEMP_INSTALL_DIR))
             .await
             .map_err(|e| e.boxed())?;
         Ok(Self {
@@ -92,10 +111,33 @@ impl Action for ProvisionNix {
             Result::<_, Box<dyn std::error::Error + Send + Sync>>::Ok(fetch_nix_clone)
         });
 
-        create_users_and_group.try_execute().await?;
-        create_nix_tree.try_execute().await?;
+        if let Err(err) = create_users_and_group.try_execute().await {
+            // `fetch_nix_handle.abort()` can't interrupt work already inside
+            // `spawn_blocking` (the tar/xz unpack is there and has no await points), so
+            // aborting and immediately removing `TEMP_INSTALL_DIR` would race an
+            // uncancellable unpack still writing into it. Instead, let the fetch actually
+            // finish (successfully or not) before cleaning up its output.
+            let _ = fetch_nix_handle.await;
+            remove_temp_install_dir().await;
+            return Err(err);
+        }
+        if let Err(err) = create_nix_tree.try_execute().await {
+            let _ = fetch_nix_handle.await;
+            remove_temp_install_dir().await;
+            return Err(err);
+        }
 
-        *fetch_nix = fetch_nix_handle.await.map_err(|e| e.boxed())??;
+        match fetch_nix_handle.await.map_err(|e| e.boxed()) {
+            Ok(Ok(completed)) => *fetch_nix = completed,
+            Ok(Err(err)) => {
+                remove_temp_install_dir().await;
+                return Err(err);
+            },
+            Err(err) => {
+                remove_temp_install_dir().await;
+                return Err(err);
+            },
+        }
         move_unpacked_nix.try_execute().await?;
 
         Ok(())
@@ -136,11 +178,16 @@ impl Action for ProvisionNix {
         });
 
         if let Err(err) = create_users_and_group.try_revert().await {
-            fetch_nix_handle.abort();
+            // See the matching comment in `execute`: wait for the fetch task to actually
+            // stop touching `TEMP_INSTALL_DIR` before removing it, since aborting it can't
+            // interrupt work already inside `spawn_blocking`.
+            let _ = fetch_nix_handle.await;
+            remove_temp_install_dir().await;
             return Err(err);
         }
         if let Err(err) = create_nix_tree.try_revert().await {
-            fetch_nix_handle.abort();
+            let _ = fetch_nix_handle.await;
+            remove_temp_install_dir().await;
             return Err(err);
         }
 
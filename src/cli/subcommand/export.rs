@@ -2,11 +2,12 @@ use std::collections::HashMap;
 use std::env;
 use std::ffi::{OsStr, OsString};
 use std::io::{stdout, Write};
-use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::path::PathBuf;
 use std::process::ExitCode;
 
 use crate::cli::CommandExecute;
+use crate::export;
 use clap::Parser;
 
 const LOCAL_STATE_DIR: &str = "/nix/var";
@@ -48,6 +49,10 @@ In `null-separated` mode, `nix-installer` emits data in this format:
 
   KEYNAME\0VALUE\0KEYNAME\0VALUE\0
 
+The `fish` and `sh` modes are `source`-able scripts for their respective shells; `nushell` and
+`powershell` work the same way, emitting `$env.KEY = "value"` and `$env:KEY = 'value'` lines
+(respectively) for `nu` and PowerShell to `source`/dot-source.
+
 */
 #[derive(Debug, Parser)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -63,6 +68,16 @@ pub struct Export {
 enum ExportFormat {
     Fish,
     Sh,
+    /// `KEYNAME\0VALUE\0KEYNAME\0VALUE\0`, for consumers that parse environment data directly
+    /// rather than `source`-ing a shell script (eg. systemd `EnvironmentFile`, CI runners).
+    #[clap(name = "null-separated")]
+    NullSeparated,
+    /// `KEYNAME VALUE\n` lines. Refuses to emit anything if a key or value is ambiguous.
+    #[clap(name = "space-newline-separated")]
+    SpaceNewlineSeparated,
+    Nushell,
+    #[clap(name = "powershell")]
+    PowerShell,
 }
 
 #[async_trait::async_trait]
@@ -100,16 +115,17 @@ impl CommandExecute for Export {
             export_env.insert(k.try_into()?, v);
         }
 
-        stdout().write_all(
-            export::escape(
-                match self.format {
-                    ExportFormat::Fish => export::Encoding::Fish,
-                    ExportFormat::Sh => export::Encoding::PosixShell,
-                },
-                export_env,
-            )?
-            .as_bytes(),
-        )?;
+        stdout().write_all(&export::escape(
+            match self.format {
+                ExportFormat::Fish => export::Encoding::Fish,
+                ExportFormat::Sh => export::Encoding::PosixShell,
+                ExportFormat::NullSeparated => export::Encoding::NullSeparated,
+                ExportFormat::SpaceNewlineSeparated => export::Encoding::SpaceNewlineSeparated,
+                ExportFormat::Nushell => export::Encoding::Nushell,
+                ExportFormat::PowerShell => export::Encoding::PowerShell,
+            },
+            export_env,
+        )?)?;
 
         Ok(ExitCode::SUCCESS)
     }
@@ -202,7 +218,19 @@ pub fn calculate_environment() -> Result<HashMap<String, OsString>, Error> {
         }
     }
 
-    if nonempty_var_os("NIX_SSL_CERT_FILE").is_none() {
+    // `nix-installer install` records the `--ssl-cert-file` it was configured with (if any) at
+    // `SSL_CERT_FILE_RECEIPT_PATH`, since this `export` process is invoked fresh by a shell's
+    // profile script and has no other way to learn what the original install was configured
+    // with. Prefer it over both a pre-existing `NIX_SSL_CERT_FILE` and the autodetected
+    // candidate list below.
+    let configured_ssl_cert_file = std::fs::read(crate::settings::SSL_CERT_FILE_RECEIPT_PATH)
+        .ok()
+        .filter(|bytes| !bytes.is_empty())
+        .map(|bytes| PathBuf::from(OsString::from_vec(bytes)))
+        .filter(|path| path.is_file());
+    if let Some(configured) = configured_ssl_cert_file {
+        envs.insert("NIX_SSL_CERT_FILE".into(), configured.into());
+    } else if nonempty_var_os("NIX_SSL_CERT_FILE").is_none() {
         let mut candidate_locations = vec![
             PathBuf::from("/etc/ssl/certs/ca-certificates.crt"), // NixOS, Ubuntu, Debian, Gentoo, Arch
             PathBuf::from("/etc/ssl/ca-bundle.pem"),             // openSUSE Tumbleweed
@@ -0,0 +1,193 @@
+//! Encoding of the environment variables computed by `nix-installer export` into a format a
+//! consumer can load, whether that's a shell `source`-ing a script or a program parsing a
+//! stream of machine-readable bytes.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+
+/// The name of an environment variable `nix-installer` computed.
+///
+/// Constructing one validates that the name is non-empty and made up only of ASCII alphanumerics
+/// and underscores, which is all [`Encoding::SpaceNewlineSeparated`] is able to emit safely.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VariableName(String);
+
+impl VariableName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn is_valid(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+}
+
+impl TryFrom<String> for VariableName {
+    type Error = InvalidVariableName;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if Self::is_valid(&value) {
+            Ok(Self(value))
+        } else {
+            Err(InvalidVariableName(value))
+        }
+    }
+}
+
+impl fmt::Display for VariableName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("`{0}` is not a valid environment variable name: it must be non-empty and contain only ASCII alphanumerics and underscores")]
+pub struct InvalidVariableName(String);
+
+/// The output format for `nix-installer export --format <Encoding>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `set -gx KEY value` lines, for `fish`.
+    Fish,
+    /// `export KEY='value'` lines, for POSIX-compatible shells (`sh`, `bash`, `zsh`, ...).
+    PosixShell,
+    /// `$env.KEY = "value"` lines, for `nushell`.
+    Nushell,
+    /// `$env:KEY = 'value'` lines, for PowerShell.
+    PowerShell,
+    /// `KEYNAME\0VALUE\0KEYNAME\0VALUE\0`, raw bytes, no escaping. Safe for any value, including
+    /// ones containing newlines.
+    NullSeparated,
+    /// `KEYNAME VALUE\n` lines. Refuses to emit anything if a key or value would be ambiguous.
+    SpaceNewlineSeparated,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("value of `{0}` contains a newline, which is not representable in `space-newline-separated` output")]
+    ValueContainsNewline(VariableName),
+    #[error("value of `{0}` is not valid UTF-8, which is required for `{1:?}` output")]
+    ValueNotUtf8(VariableName, Encoding),
+}
+
+/// Encode `vars` as `encoding`, ready to be written to stdout.
+pub fn escape(
+    encoding: Encoding,
+    vars: HashMap<VariableName, OsString>,
+) -> Result<Vec<u8>, Error> {
+    let mut vars: Vec<(VariableName, OsString)> = vars.into_iter().collect();
+    vars.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    match encoding {
+        Encoding::Fish => {
+            let mut out = String::new();
+            for (name, value) in vars {
+                let value = utf8_value(&name, encoding, &value)?;
+                out.push_str(&format!("set -gx {name} \"{}\";\n", escape_double_quoted(value)));
+            }
+            Ok(out.into_bytes())
+        },
+        Encoding::PosixShell => {
+            let mut out = String::new();
+            for (name, value) in vars {
+                let value = utf8_value(&name, encoding, &value)?;
+                out.push_str(&format!("export {name}='{}'\n", escape_single_quoted(value)));
+            }
+            Ok(out.into_bytes())
+        },
+        Encoding::Nushell => {
+            let mut out = String::new();
+            for (name, value) in vars {
+                let value = utf8_value(&name, encoding, &value)?;
+                if is_path_list_variable(&name) {
+                    let items = split_path_list(value)
+                        .map(|item| format!("\"{}\"", escape_double_quoted(item)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    out.push_str(&format!("$env.{name} = [{items}]\n"));
+                } else {
+                    out.push_str(&format!(
+                        "$env.{name} = \"{}\"\n",
+                        escape_double_quoted(value)
+                    ));
+                }
+            }
+            Ok(out.into_bytes())
+        },
+        Encoding::PowerShell => {
+            let mut out = String::new();
+            for (name, value) in vars {
+                let value = utf8_value(&name, encoding, &value)?;
+                let value = if is_path_list_variable(&name) {
+                    split_path_list(value).collect::<Vec<_>>().join(";")
+                } else {
+                    value.to_string()
+                };
+                out.push_str(&format!(
+                    "$env:{name} = '{}'\n",
+                    escape_powershell_single_quoted(&value)
+                ));
+            }
+            Ok(out.into_bytes())
+        },
+        Encoding::NullSeparated => {
+            let mut out: Vec<u8> = Vec::new();
+            for (name, value) in vars {
+                out.extend_from_slice(name.as_str().as_bytes());
+                out.push(0);
+                out.extend_from_slice(value.as_bytes());
+                out.push(0);
+            }
+            Ok(out)
+        },
+        Encoding::SpaceNewlineSeparated => {
+            let mut out = String::new();
+            for (name, value) in vars {
+                let value = utf8_value(&name, encoding, &value)?;
+                if value.contains('\n') {
+                    return Err(Error::ValueContainsNewline(name));
+                }
+                out.push_str(&format!("{name} {value}\n"));
+            }
+            Ok(out.into_bytes())
+        },
+    }
+}
+
+fn utf8_value<'a>(
+    name: &VariableName,
+    encoding: Encoding,
+    value: &'a OsString,
+) -> Result<&'a str, Error> {
+    value
+        .to_str()
+        .ok_or_else(|| Error::ValueNotUtf8(name.clone(), encoding))
+}
+
+fn escape_double_quoted(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_single_quoted(value: &str) -> String {
+    value.replace('\'', r"'\''")
+}
+
+fn escape_powershell_single_quoted(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+/// Variables `nix-installer` computes as a `:`-joined list of paths, which non-POSIX shells
+/// need re-joined (or rendered as a list) using their own list conventions, rather than treated
+/// as an opaque string.
+fn is_path_list_variable(name: &VariableName) -> bool {
+    matches!(name.as_str(), "PATH" | "MANPATH" | "XDG_DATA_DIRS")
+}
+
+fn split_path_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(':').filter(|item| !item.is_empty())
+}
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use url::Url;
+
+/// Where `nix-installer install` records the `--ssl-cert-file` it was configured with, so that
+/// a later, independent `nix-installer export` process (run fresh by a shell's profile script on
+/// every new shell) can still prefer it over the autodetected CA bundle candidates. An
+/// environment variable can't carry this across processes since `export` is invoked by a new
+/// shell long after `install` has exited.
+pub const SSL_CERT_FILE_RECEIPT_PATH: &str = "/nix/var/nix-installer/configured-ssl-cert-file";
+
+/// A URL pointing at a resource to fetch, or a path to a copy of that resource already present
+/// on the local filesystem.
+///
+/// This lets install steps that would normally fetch something over HTTP(S) instead accept a
+/// pre-staged local file (or a `file://` URL, which is treated the same way), so offline /
+/// air-gapped installs don't need a reachable network.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum UrlOrPath {
+    Url(Url),
+    Path(PathBuf),
+}
+
+impl UrlOrPath {
+    /// If this is a local path (or a `file://` URL), return the path.
+    pub fn as_local_path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Path(path) => Some(path.clone()),
+            Self::Url(url) if url.scheme() == "file" => url.to_file_path().ok(),
+            Self::Url(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for UrlOrPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Url(url) => write!(f, "{url}"),
+            Self::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl std::str::FromStr for UrlOrPath {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(url) = Url::parse(s) {
+            // A single-letter scheme (eg. `C:\path`) is almost certainly a Windows drive letter,
+            // not a URL scheme.
+            if url.scheme().len() > 1 {
+                return Ok(Self::Url(url));
+            }
+        }
+
+        Ok(Self::Path(PathBuf::from(s)))
+    }
+}
+
+/// Settings which are common across all installation modes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, clap::Parser)]
+pub struct CommonSettings {
+    /// Where the Nix package archive should be fetched from, either as a URL (`https://...` or
+    /// `file://...`) or as a bare path to a tarball already present on disk.
+    #[clap(
+        long,
+        env = "NIX_INSTALLER_NIX_PACKAGE_URL",
+        default_value = "https://releases.nixos.org/nix/nix-2.21.2/nix-2.21.2-x86_64-linux.tar.xz"
+    )]
+    pub nix_package_url: UrlOrPath,
+
+    /// An SSL certificate authority file (in PEM format) to use when fetching the Nix package
+    /// archive, for use behind corporate proxies that perform TLS interception.
+    #[clap(long, env = "NIX_INSTALLER_SSL_CERT_FILE")]
+    pub ssl_cert_file: Option<PathBuf>,
+
+    /// An HTTP(S) proxy to use when fetching the Nix package archive, eg.
+    /// `https://proxy.example.com:8080`.
+    #[clap(long, env = "NIX_INSTALLER_PROXY")]
+    pub proxy: Option<Url>,
+
+    /// The SRI hash (eg. `sha256-AAAA...`) or bare SHA-256 hex digest the fetched Nix package
+    /// archive is expected to have. If set, the archive is verified against this digest before
+    /// being unpacked, and the install aborts on mismatch.
+    #[clap(long, env = "NIX_INSTALLER_NIX_PACKAGE_SHA256")]
+    pub nix_package_sha256: Option<String>,
+}
+
+impl CommonSettings {
+    pub fn nix_package_url(&mut self, nix_package_url: UrlOrPath) -> &mut Self {
+        self.nix_package_url = nix_package_url;
+        self
+    }
+
+    pub fn ssl_cert_file(&mut self, ssl_cert_file: impl Into<Option<PathBuf>>) -> &mut Self {
+        self.ssl_cert_file = ssl_cert_file.into();
+        self
+    }
+
+    pub fn proxy(&mut self, proxy: impl Into<Option<Url>>) -> &mut Self {
+        self.proxy = proxy.into();
+        self
+    }
+
+    pub fn nix_package_sha256(
+        &mut self,
+        nix_package_sha256: impl Into<Option<String>>,
+    ) -> &mut Self {
+        self.nix_package_sha256 = nix_package_sha256.into();
+        self
+    }
+}